@@ -1,9 +1,164 @@
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use syn::{
     FnArg, ImplItem, ItemFn, ItemImpl, Type, parse::Parse, parse::ParseStream, parse_macro_input,
 };
 
+/// Best-effort syntactic check for `Result<T, E>`: does the return type's
+/// last path segment look like `Result` with two generic type args? Returns
+/// the `(T, E)` types if so. Anything else (including `Result<T>` with a
+/// defaulted error type, or a type alias for `Result`) is simply not
+/// recognized, in which case callers fall back to only emitting the generic
+/// `_returning` helper.
+fn result_ok_err_types(ty: &Type) -> Option<(Type, Type)> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    let mut type_args = args.args.iter().filter_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty.clone()),
+        _ => None,
+    });
+    let ok_ty = type_args.next()?;
+    let err_ty = type_args.next()?;
+    Some((ok_ty, err_ty))
+}
+
+/// A borrowed type this macro knows how to convert to and from an owned
+/// equivalent, so mocks can deal in plain owned values instead of juggling
+/// borrows with the right lifetime — which for return types isn't just
+/// convenience: a closure has no way to conjure a borrow that doesn't come
+/// from one of its arguments.
+enum OwnedKind {
+    Str,
+    Path,
+    CStr,
+    OsStr,
+    Slice(Box<Type>),
+}
+
+impl OwnedKind {
+    fn owned_type(&self) -> TokenStream2 {
+        match self {
+            OwnedKind::Str => quote! { ::std::string::String },
+            OwnedKind::Path => quote! { ::std::path::PathBuf },
+            OwnedKind::CStr => quote! { ::std::ffi::CString },
+            OwnedKind::OsStr => quote! { ::std::ffi::OsString },
+            OwnedKind::Slice(elem) => quote! { ::std::vec::Vec<#elem> },
+        }
+    }
+
+    /// Expression converting a borrowed value (`expr`) to its owned
+    /// equivalent, to pass to a mock that now expects the owned type.
+    fn to_owned_expr(&self, expr: &TokenStream2) -> TokenStream2 {
+        match self {
+            OwnedKind::Str => quote! { #expr.to_string() },
+            OwnedKind::Path => quote! { #expr.to_path_buf() },
+            OwnedKind::CStr => quote! { #expr.to_owned() },
+            OwnedKind::OsStr => quote! { #expr.to_os_string() },
+            OwnedKind::Slice(_) => quote! { #expr.to_vec() },
+        }
+    }
+
+    /// Expression borrowing back from an owned value (`expr`), to satisfy
+    /// the original (borrowed) return type once the mock has produced its
+    /// owned result.
+    fn borrow_expr(&self, expr: TokenStream2) -> TokenStream2 {
+        // There's no owned value already lying around with the right
+        // lifetime to borrow from - the mock just produced a fresh one - so
+        // it's leaked to get a `'static` borrow, which satisfies whatever
+        // (necessarily shorter) lifetime the original signature needs.
+        let leaked = quote! { ::std::boxed::Box::leak(::std::boxed::Box::new(#expr)) };
+        match self {
+            OwnedKind::Str => quote! { #leaked.as_str() },
+            OwnedKind::Path => quote! { #leaked.as_path() },
+            OwnedKind::CStr => quote! { #leaked.as_c_str() },
+            OwnedKind::OsStr => quote! { #leaked.as_os_str() },
+            OwnedKind::Slice(_) => quote! { #leaked.as_slice() },
+        }
+    }
+}
+
+/// Recognizes `&str`, `&Path`, `&CStr`, `&OsStr` and `&[T]`. `&'static`
+/// references are left alone, since those the caller can legitimately
+/// supply directly; anything else (including `&mut` references and
+/// arbitrary `&T`) is also left alone.
+fn owned_conversion(ty: &Type) -> Option<OwnedKind> {
+    let Type::Reference(r) = ty else {
+        return None;
+    };
+    if r.mutability.is_some() {
+        return None;
+    }
+    if let Some(lifetime) = &r.lifetime {
+        if lifetime.ident == "static" {
+            return None;
+        }
+    }
+    if let Type::Slice(slice) = &*r.elem {
+        return Some(OwnedKind::Slice(slice.elem.clone()));
+    }
+    let Type::Path(type_path) = &*r.elem else {
+        return None;
+    };
+    match type_path.path.segments.last()?.ident.to_string().as_str() {
+        "str" => Some(OwnedKind::Str),
+        "Path" => Some(OwnedKind::Path),
+        "CStr" => Some(OwnedKind::CStr),
+        "OsStr" => Some(OwnedKind::OsStr),
+        _ => None,
+    }
+}
+
+/// A parameter or return type alongside the mock-facing owned type it was
+/// normalized to (identical to the original when no conversion applies).
+struct OwnedSlot {
+    mock_type: TokenStream2,
+    conversion: Option<OwnedKind>,
+}
+
+fn owned_slot(ty: &Type) -> OwnedSlot {
+    match owned_conversion(ty) {
+        Some(kind) => OwnedSlot {
+            mock_type: kind.owned_type(),
+            conversion: Some(kind),
+        },
+        None => OwnedSlot {
+            mock_type: quote! { #ty },
+            conversion: None,
+        },
+    }
+}
+
+/// The type a `_when` matcher sees for one parameter: a shared reference to
+/// it, so the matcher can inspect the argument without taking ownership.
+/// If the parameter is already `&T`, that's used as-is rather than doubling
+/// up to `&&T`; anything else (owned values, `&mut T`) is borrowed with an
+/// extra `&`.
+fn matcher_param_type(ty: &Type) -> TokenStream2 {
+    match ty {
+        Type::Reference(r) if r.mutability.is_none() => quote! { #ty },
+        _ => quote! { &#ty },
+    }
+}
+
+/// Expression borrowing `name` (a call argument of type `ty`) the same way
+/// `matcher_param_type(ty)` types it - a no-op for already-`&T` parameters,
+/// an added `&` otherwise.
+fn matcher_arg_expr(name: &syn::Ident, ty: &Type) -> TokenStream2 {
+    match ty {
+        Type::Reference(r) if r.mutability.is_none() => quote! { #name },
+        _ => quote! { &#name },
+    }
+}
+
 /// Enum that holds either a function or an impl block
 enum MockableItem {
     Fn(ItemFn),
@@ -109,27 +264,236 @@ fn generate_mockable_fn(input_fn: ItemFn) -> TokenStream {
         })
         .collect();
 
-    let return_type = match &sig.output {
-        syn::ReturnType::Default => quote! { () },
-        syn::ReturnType::Type(_, ty) => quote! { #ty },
+    let return_ty: Option<&Type> = match &sig.output {
+        syn::ReturnType::Default => None,
+        syn::ReturnType::Type(_, ty) => Some(ty),
+    };
+    let result_types = return_ty.and_then(result_ok_err_types);
+
+    // Mocks deal in owned types where the original signature uses a
+    // (non-`'static`) borrowed type: for parameters, so mocks don't have to
+    // juggle borrows; for the return type, because a closure has no way to
+    // conjure a borrow that doesn't come from one of its own arguments.
+    let param_slots: Vec<OwnedSlot> = param_types.iter().map(|ty| owned_slot(ty)).collect();
+    let mock_param_types: Vec<&TokenStream2> = param_slots.iter().map(|s| &s.mock_type).collect();
+    let param_adapters: Vec<TokenStream2> = param_names
+        .iter()
+        .zip(&param_slots)
+        .map(|(name, slot)| match &slot.conversion {
+            Some(kind) => kind.to_owned_expr(&quote! { #name }),
+            None => quote! { #name },
+        })
+        .collect();
+
+    let return_slot = match return_ty {
+        Some(ty) => owned_slot(ty),
+        None => OwnedSlot {
+            mock_type: quote! { () },
+            conversion: None,
+        },
+    };
+    let mock_return_type = &return_slot.mock_type;
+    let adapt_mock_result = |expr: TokenStream2| match &return_slot.conversion {
+        Some(kind) => kind.borrow_expr(expr),
+        None => expr,
     };
 
-    let mock_fn_type = if param_types.is_empty() {
-        quote! { dyn Fn() -> #return_type + Send + Sync }
+    // Every mock kind for a function - `Fn`-based or `FnMut`-based alike -
+    // is stored as the same `Mutex`-wrapped `dyn FnMut` trait object, so they
+    // all live as entries of one `MockList` instead of splitting across two
+    // separate registries under the same key (a `Fn` closure already
+    // implements `FnMut` via its blanket impl, so wrapping one in a `Mutex`
+    // costs nothing but the lock).
+    let mock_fn_type = if mock_param_types.is_empty() {
+        quote! { ::std::sync::Mutex<dyn FnMut() -> #mock_return_type + Send> }
     } else {
-        quote! { dyn Fn(#(#param_types),*) -> #return_type + Send + Sync }
+        quote! { ::std::sync::Mutex<dyn FnMut(#(#mock_param_types),*) -> #mock_return_type + Send> }
     };
 
     let set_mock_helper = syn::Ident::new(&format!("set_mock_{}", fn_name), fn_name.span());
+    let set_mock_returning_helper =
+        syn::Ident::new(&format!("set_mock_{}_returning", fn_name), fn_name.span());
+    let set_mock_mut_helper = syn::Ident::new(&format!("set_mock_{}_mut", fn_name), fn_name.span());
+    let set_mock_seq_helper = syn::Ident::new(&format!("set_mock_{}_seq", fn_name), fn_name.span());
+    let set_mock_when_helper = syn::Ident::new(&format!("set_mock_{}_when", fn_name), fn_name.span());
 
     let mock_key = quote! { concat!(module_path!(), "::", #fn_name_str) };
 
+    let ignored_params: Vec<_> = mock_param_types.iter().map(|ty| quote! { _: #ty }).collect();
+
+    // The matcher for `_when` mocks (and the catch-all matcher used by the
+    // plain `set_mock`/`_returning`/`_ok`/`_err` helpers) is itself a `dyn
+    // Fn` trait object shaped like the call's parameter list, one argument
+    // per original (non-owned-converted) parameter, returning `bool`. Unlike
+    // a tuple of argument references, this is `'static` regardless of the
+    // call site's own borrow lifetimes - Rust elides each `&_` parameter to
+    // a higher-ranked `for<'r> Fn(&'r _) -> bool`.
+    let matcher_param_types: Vec<TokenStream2> =
+        param_types.iter().map(|ty| matcher_param_type(ty)).collect();
+    let matcher_fn_type = if matcher_param_types.is_empty() {
+        quote! { dyn Fn() -> bool + Send + Sync }
+    } else {
+        quote! { dyn Fn(#(#matcher_param_types),*) -> bool + Send + Sync }
+    };
+    let matcher_args: Vec<TokenStream2> = param_names
+        .iter()
+        .zip(param_types.iter())
+        .map(|(name, ty)| matcher_arg_expr(name, ty))
+        .collect();
+    let ignored_matcher_params: Vec<_> = matcher_param_types
+        .iter()
+        .map(|ty| quote! { _: #ty })
+        .collect();
+
+    let when_helper = quote! {
+        /// Like `set_mock_*`, but only applies when `matcher` returns true
+        /// for the call arguments (passed by reference, in declaration
+        /// order). Entries are tried in install order, so layering a
+        /// catch-all mock after one or more `_when` mocks makes it their
+        /// fallback. Unlike a catch-all, two `_when` guards alive at once
+        /// both stay installed - reassigning a condition doesn't replace an
+        /// earlier one, so drop the first guard before installing another
+        /// for the same condition.
+        #[cfg(test)]
+        #[allow(dead_code)]
+        pub fn #set_mock_when_helper<M, F>(matcher: M, mock: F) -> ::fnmock::MockGuard
+        where
+            M: Fn(#(#matcher_param_types),*) -> bool + Send + Sync + 'static,
+            F: Fn(#(#mock_param_types),*) -> #mock_return_type + Send + Sync + 'static,
+        {
+            let arc_matcher: ::std::sync::Arc<#matcher_fn_type> = ::std::sync::Arc::new(matcher);
+            let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                ::std::sync::Arc::new(::std::sync::Mutex::new(mock));
+            ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                #mock_key, arc_matcher, arc_mock, false,
+            )
+        }
+    };
+
+    let returning_helper = quote! {
+        #[cfg(test)]
+        #[allow(dead_code)]
+        pub fn #set_mock_returning_helper(value: #mock_return_type) -> ::fnmock::MockGuard
+        where
+            #mock_return_type: Clone + Send + Sync + 'static,
+        {
+            let arc_mock: ::std::sync::Arc<#mock_fn_type> = ::std::sync::Arc::new(
+                ::std::sync::Mutex::new(move |#(#ignored_params),*| value.clone()),
+            );
+            let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+            ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                #mock_key,
+                arc_matcher,
+                arc_mock,
+                true,
+            )
+        }
+    };
+
+    let ok_err_helpers = match &result_types {
+        Some((ok_ty, err_ty)) => {
+            let set_mock_ok_helper =
+                syn::Ident::new(&format!("set_mock_{}_ok", fn_name), fn_name.span());
+            let set_mock_err_helper =
+                syn::Ident::new(&format!("set_mock_{}_err", fn_name), fn_name.span());
+            quote! {
+                #[cfg(test)]
+                #[allow(dead_code)]
+                pub fn #set_mock_ok_helper(value: #ok_ty) -> ::fnmock::MockGuard
+                where
+                    #ok_ty: Clone + Send + Sync + 'static,
+                    #err_ty: Send + Sync + 'static,
+                {
+                    let arc_mock: ::std::sync::Arc<#mock_fn_type> = ::std::sync::Arc::new(
+                        ::std::sync::Mutex::new(move |#(#ignored_params),*| Ok(value.clone())),
+                    );
+                    let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                        ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                    ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                        #mock_key,
+                        arc_matcher,
+                        arc_mock,
+                        true,
+                    )
+                }
+
+                #[cfg(test)]
+                #[allow(dead_code)]
+                pub fn #set_mock_err_helper(value: #err_ty) -> ::fnmock::MockGuard
+                where
+                    #ok_ty: Send + Sync + 'static,
+                    #err_ty: Clone + Send + Sync + 'static,
+                {
+                    let arc_mock: ::std::sync::Arc<#mock_fn_type> = ::std::sync::Arc::new(
+                        ::std::sync::Mutex::new(move |#(#ignored_params),*| Err(value.clone())),
+                    );
+                    let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                        ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                    ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                        #mock_key,
+                        arc_matcher,
+                        arc_mock,
+                        true,
+                    )
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    // `FnMut` mocks (and the `_seq` helper built on top of them) serialize
+    // concurrent calls through a `Mutex`, since an `FnMut` can't be shared
+    // across threads like `Fn` can. They're installed as a catch-all
+    // `MockList` entry the same way the other helpers above are, rather than
+    // through a separate registry, so a `_mut`/`_seq` mock and a `_when`/
+    // plain mock for the same function can't silently clobber each other.
+    let fnmut_helpers = quote! {
+        /// Like `set_mock_*`, but the mock may mutate its own state across
+        /// calls. Concurrent calls are serialized through a `Mutex`.
+        #[cfg(test)]
+        #[allow(dead_code)]
+        pub fn #set_mock_mut_helper<F>(mock: F) -> ::fnmock::MockGuard
+        where
+            F: FnMut(#(#mock_param_types),*) -> #mock_return_type + Send + 'static,
+        {
+            let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                ::std::sync::Arc::new(::std::sync::Mutex::new(mock));
+            let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+            ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                #mock_key,
+                arc_matcher,
+                arc_mock,
+                true,
+            )
+        }
+
+        /// Yields each value in `values` once, in order, then panics. Calls
+        /// are serialized through a `Mutex`.
+        #[cfg(test)]
+        #[allow(dead_code)]
+        pub fn #set_mock_seq_helper(values: ::std::vec::Vec<#mock_return_type>) -> ::fnmock::MockGuard
+        where
+            #mock_return_type: Send + 'static,
+        {
+            let mut values = values.into_iter();
+            #set_mock_mut_helper(move |#(#ignored_params),*| {
+                values
+                    .next()
+                    .expect(concat!("mock `", #fn_name_str, "` sequence exhausted"))
+            })
+        }
+    };
+
     let original_call = if is_async {
         quote! { #original_fn_name(#(#param_names),*).await }
     } else {
         quote! { #original_fn_name(#(#param_names),*) }
     };
 
+    let mock_result = adapt_mock_result(quote! { (&mut *mock_fn)(#(#param_adapters),*) });
+
     let expanded = quote! {
         // Original function - `cfg(not(test))` builds
         #(#attrs)*
@@ -140,23 +504,47 @@ fn generate_mockable_fn(input_fn: ItemFn) -> TokenStream {
         #[cfg(test)]
         #vis #original_sig #block
 
-        // Helper function to set mocks with automatic type conversion
+        // Helper function to set mocks with automatic type conversion.
+        // Installing one of these while a previous guard from this helper
+        // (or `_returning`/`_ok`/`_err`/`_mut`) is still alive replaces it,
+        // matching the old single-slot registry's "last write wins".
         #[cfg(test)]
         #[allow(dead_code)]
         pub fn #set_mock_helper<F>(mock: F) -> ::fnmock::MockGuard
         where
-            F: Fn(#(#param_types),*) -> #return_type + Send + Sync + 'static,
+            F: Fn(#(#mock_param_types),*) -> #mock_return_type + Send + Sync + 'static,
         {
-            let arc_mock: ::std::sync::Arc<#mock_fn_type> = ::std::sync::Arc::new(mock);
-            ::fnmock::MockRegistry::set_mock(#mock_key, arc_mock)
+            let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                ::std::sync::Arc::new(::std::sync::Mutex::new(mock));
+            let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+            ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                #mock_key,
+                arc_matcher,
+                arc_mock,
+                true,
+            )
         }
 
+        // Helpers for the common "always return this value"/"always error" cases
+        #returning_helper
+        #ok_err_helpers
+        #fnmut_helpers
+        #when_helper
+
         // Wrapper function in test mode
         #[cfg(test)]
         #(#attrs)*
         #vis #sig {
-            if let Some(mock_fn) = ::fnmock::MockRegistry::get_mock::<#mock_fn_type>(#mock_key) {
-                return mock_fn(#(#param_names),*);
+            if let Some((mock_fn, mock_count)) =
+                ::fnmock::MockRegistry::get_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                    #mock_key,
+                    |__fnmock_matcher| __fnmock_matcher(#(#matcher_args),*),
+                )
+            {
+                mock_count.fetch_add(1, ::std::sync::atomic::Ordering::SeqCst);
+                let mut mock_fn = mock_fn.lock().unwrap();
+                return #mock_result;
             }
             #original_call
         }
@@ -173,8 +561,20 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
 
     let type_name = quote!(#self_ty).to_string().replace(' ', "");
 
+    // `impl Trait for Type { .. }` carries its trait path in `trait_`; fold it
+    // into the mock key and preserve the `for Trait` header so trait impls
+    // keep being valid trait impls once mocked.
+    let trait_name = input_impl
+        .trait_
+        .as_ref()
+        .map(|(_, path, _)| quote!(#path).to_string().replace(' ', ""));
+    let trait_for = input_impl.trait_.as_ref().map(|(bang, path, for_token)| {
+        quote! { #bang #path #for_token }
+    });
+
     let mut test_items = Vec::new();
     let mut non_test_items = Vec::new();
+    let mut original_items = Vec::new();
     let mut helper_functions = Vec::new();
 
     for item in &input_impl.items {
@@ -225,17 +625,66 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
                 })
                 .collect();
 
-            let return_type = match &sig.output {
-                syn::ReturnType::Default => quote! { () },
-                syn::ReturnType::Type(_, ty) => quote! { #ty },
+            let return_ty: Option<&Type> = match &sig.output {
+                syn::ReturnType::Default => None,
+                syn::ReturnType::Type(_, ty) => Some(ty),
+            };
+            let result_types = return_ty.and_then(result_ok_err_types);
+
+            // Mocks deal in owned types where the original signature uses a
+            // (non-`'static`) borrowed type, same as for free functions. The
+            // receiver itself is left alone - manufacturing an owned `Self`
+            // out of thin air isn't something we can do generically.
+            let param_slots: Vec<OwnedSlot> = param_types.iter().map(|ty| owned_slot(ty)).collect();
+            let mock_param_types: Vec<&TokenStream2> =
+                param_slots.iter().map(|s| &s.mock_type).collect();
+            let param_adapters: Vec<TokenStream2> = param_names
+                .iter()
+                .zip(&param_slots)
+                .map(|(name, slot)| match &slot.conversion {
+                    Some(kind) => kind.to_owned_expr(&quote! { #name }),
+                    None => quote! { #name },
+                })
+                .collect();
+
+            let return_slot = match return_ty {
+                Some(ty) => owned_slot(ty),
+                None => OwnedSlot {
+                    mock_type: quote! { () },
+                    conversion: None,
+                },
+            };
+            let mock_return_type = &return_slot.mock_type;
+            let adapt_mock_result = |expr: TokenStream2| match &return_slot.conversion {
+                Some(kind) => kind.borrow_expr(expr),
+                None => expr,
             };
 
-            // Generate mock key with type name included
-            let mock_key = quote! { concat!(module_path!(), "::", #type_name, "::", #fn_name_str) };
+            // Generate mock key with type (and, for trait impls, trait) name included
+            let mock_key = match &trait_name {
+                Some(trait_name) => {
+                    quote! { concat!(module_path!(), "::<", #trait_name, " as ", #type_name, ">::", #fn_name_str) }
+                }
+                None => {
+                    quote! { concat!(module_path!(), "::", #type_name, "::", #fn_name_str) }
+                }
+            };
 
             let set_mock_helper = syn::Ident::new(&format!("set_mock_{}", fn_name), fn_name.span());
+            let set_mock_when_helper =
+                syn::Ident::new(&format!("set_mock_{}_when", fn_name), fn_name.span());
 
-            let (mock_fn_type, mock_call, helper_where) = if has_receiver {
+            let (
+                mock_fn_type,
+                mock_call,
+                helper_where,
+                mut_helper_where,
+                ignored_params,
+                matcher_fn_type,
+                matcher_where,
+                ignored_matcher_params,
+                matcher_call,
+            ) = if has_receiver {
                 // For methods with self, the mock receives a reference to Self
                 let receiver = sig.inputs.first().unwrap();
                 let receiver_type = match receiver {
@@ -253,34 +702,127 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
                     _ => quote! { &#self_ty },
                 };
 
-                let fn_type = if param_types.is_empty() {
-                    quote! { dyn Fn(#receiver_type) -> #return_type + Send + Sync }
+                // Every mock kind for a method - `Fn`-based or `FnMut`-based
+                // alike - is stored as the same `Mutex`-wrapped `dyn FnMut`
+                // trait object, so they all live as entries of one
+                // `MockList` instead of splitting across separate registries
+                // under the same key.
+                let fn_type = if mock_param_types.is_empty() {
+                    quote! { ::std::sync::Mutex<dyn FnMut(#receiver_type) -> #mock_return_type + Send> }
                 } else {
-                    quote! { dyn Fn(#receiver_type, #(#param_types),*) -> #return_type + Send + Sync }
+                    quote! { ::std::sync::Mutex<dyn FnMut(#receiver_type, #(#mock_param_types),*) -> #mock_return_type + Send> }
                 };
 
-                let call = quote! { mock_fn(self, #(#param_names),*) };
+                let call = adapt_mock_result(quote! { (&mut *mock_fn)(self, #(#param_adapters),*) });
 
                 let helper_where = quote! {
-                    F: Fn(#receiver_type, #(#param_types),*) -> #return_type + Send + Sync + 'static
+                    F: Fn(#receiver_type, #(#mock_param_types),*) -> #mock_return_type + Send + Sync + 'static
+                };
+                let mut_helper_where = quote! {
+                    F: FnMut(#receiver_type, #(#mock_param_types),*) -> #mock_return_type + Send + 'static
                 };
 
-                (fn_type, call, helper_where)
+                let mut ignored_params = vec![quote! { _: #receiver_type }];
+                ignored_params.extend(mock_param_types.iter().map(|ty| quote! { _: #ty }));
+
+                // As with free functions, the matcher is a `dyn Fn` trait
+                // object shaped like the call's parameter list (receiver
+                // first, always as `&Self`), not a tuple of argument
+                // references, so it stays `'static` regardless of the call
+                // site's borrow lifetimes.
+                let matcher_receiver_type = quote! { &#self_ty };
+                let matcher_self = match receiver {
+                    FnArg::Receiver(r) if r.reference.is_none() => quote! { &self },
+                    _ => quote! { &*self },
+                };
+                let matcher_param_types: Vec<TokenStream2> =
+                    param_types.iter().map(|ty| matcher_param_type(ty)).collect();
+                let matcher_fn_type = if matcher_param_types.is_empty() {
+                    quote! { dyn Fn(#matcher_receiver_type) -> bool + Send + Sync }
+                } else {
+                    quote! { dyn Fn(#matcher_receiver_type, #(#matcher_param_types),*) -> bool + Send + Sync }
+                };
+                let matcher_where = if matcher_param_types.is_empty() {
+                    quote! { M: Fn(#matcher_receiver_type) -> bool + Send + Sync + 'static }
+                } else {
+                    quote! { M: Fn(#matcher_receiver_type, #(#matcher_param_types),*) -> bool + Send + Sync + 'static }
+                };
+                let mut ignored_matcher_params = vec![quote! { _: #matcher_receiver_type }];
+                ignored_matcher_params.extend(matcher_param_types.iter().map(|ty| quote! { _: #ty }));
+                let matcher_args: Vec<TokenStream2> = param_names
+                    .iter()
+                    .zip(param_types.iter())
+                    .map(|(name, ty)| matcher_arg_expr(name, ty))
+                    .collect();
+                let matcher_call = quote! { __fnmock_matcher(#matcher_self, #(#matcher_args),*) };
+
+                (
+                    fn_type,
+                    call,
+                    helper_where,
+                    mut_helper_where,
+                    ignored_params,
+                    matcher_fn_type,
+                    matcher_where,
+                    ignored_matcher_params,
+                    matcher_call,
+                )
             } else {
                 // Static method / associated function
-                let fn_type = if param_types.is_empty() {
-                    quote! { dyn Fn() -> #return_type + Send + Sync }
+                //
+                // As above, every mock kind shares one Mutex-wrapped
+                // `dyn FnMut` storage type.
+                let fn_type = if mock_param_types.is_empty() {
+                    quote! { ::std::sync::Mutex<dyn FnMut() -> #mock_return_type + Send> }
                 } else {
-                    quote! { dyn Fn(#(#param_types),*) -> #return_type + Send + Sync }
+                    quote! { ::std::sync::Mutex<dyn FnMut(#(#mock_param_types),*) -> #mock_return_type + Send> }
                 };
 
-                let call = quote! { mock_fn(#(#param_names),*) };
+                let call = adapt_mock_result(quote! { (&mut *mock_fn)(#(#param_adapters),*) });
 
                 let helper_where = quote! {
-                    F: Fn(#(#param_types),*) -> #return_type + Send + Sync + 'static
+                    F: Fn(#(#mock_param_types),*) -> #mock_return_type + Send + Sync + 'static
+                };
+                let mut_helper_where = quote! {
+                    F: FnMut(#(#mock_param_types),*) -> #mock_return_type + Send + 'static
                 };
 
-                (fn_type, call, helper_where)
+                let ignored_params = mock_param_types.iter().map(|ty| quote! { _: #ty }).collect();
+
+                let matcher_param_types: Vec<TokenStream2> =
+                    param_types.iter().map(|ty| matcher_param_type(ty)).collect();
+                let matcher_fn_type = if matcher_param_types.is_empty() {
+                    quote! { dyn Fn() -> bool + Send + Sync }
+                } else {
+                    quote! { dyn Fn(#(#matcher_param_types),*) -> bool + Send + Sync }
+                };
+                let matcher_where = if matcher_param_types.is_empty() {
+                    quote! { M: Fn() -> bool + Send + Sync + 'static }
+                } else {
+                    quote! { M: Fn(#(#matcher_param_types),*) -> bool + Send + Sync + 'static }
+                };
+                let ignored_matcher_params: Vec<_> = matcher_param_types
+                    .iter()
+                    .map(|ty| quote! { _: #ty })
+                    .collect();
+                let matcher_args: Vec<TokenStream2> = param_names
+                    .iter()
+                    .zip(param_types.iter())
+                    .map(|(name, ty)| matcher_arg_expr(name, ty))
+                    .collect();
+                let matcher_call = quote! { __fnmock_matcher(#(#matcher_args),*) };
+
+                (
+                    fn_type,
+                    call,
+                    helper_where,
+                    mut_helper_where,
+                    ignored_params,
+                    matcher_fn_type,
+                    matcher_where,
+                    ignored_matcher_params,
+                    matcher_call,
+                )
             };
 
             // Call to the original function
@@ -297,8 +839,11 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
                 #vis #sig #block
             });
 
-            // Test version - renamed original
-            test_items.push(quote! {
+            // Test version - renamed original. Lives in its own inherent
+            // impl block (see below) rather than alongside the wrapper,
+            // since for a trait impl, the trait impl block may only contain
+            // members of the trait itself.
+            original_items.push(quote! {
                 #(#attrs)*
                 #vis #original_sig #block
             });
@@ -307,14 +852,24 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
             test_items.push(quote! {
                 #(#attrs)*
                 #vis #sig {
-                    if let Some(mock_fn) = ::fnmock::MockRegistry::get_mock::<#mock_fn_type>(#mock_key) {
+                    if let Some((mock_fn, mock_count)) =
+                        ::fnmock::MockRegistry::get_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                            #mock_key,
+                            |__fnmock_matcher| #matcher_call,
+                        )
+                    {
+                        mock_count.fetch_add(1, ::std::sync::atomic::Ordering::SeqCst);
+                        let mut mock_fn = mock_fn.lock().unwrap();
                         return #mock_call;
                     }
                     #original_call
                 }
             });
 
-            // Helper function for setting mock
+            // Helper function for setting mock. Installing one of these
+            // while a previous guard from this helper (or
+            // `_returning`/`_ok`/`_err`/`_mut`) is still alive replaces it,
+            // matching the old single-slot registry's "last write wins".
             helper_functions.push(quote! {
                 #[cfg(test)]
                 #[allow(dead_code)]
@@ -322,8 +877,160 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
                 where
                     #helper_where,
                 {
-                    let arc_mock: ::std::sync::Arc<#mock_fn_type> = ::std::sync::Arc::new(mock);
-                    ::fnmock::MockRegistry::set_mock(#mock_key, arc_mock)
+                    let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                        ::std::sync::Arc::new(::std::sync::Mutex::new(mock));
+                    let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                        ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                    ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                        #mock_key,
+                        arc_matcher,
+                        arc_mock,
+                        true,
+                    )
+                }
+            });
+
+            // Like `set_mock_*`, but only applies when `matcher` returns true
+            // for the call arguments (passed by reference, in declaration
+            // order, with `self` first for methods). Entries are tried in
+            // install order, so layering a catch-all mock after one or more
+            // `_when` mocks makes it their fallback. Unlike a catch-all, two
+            // `_when` guards alive at once both stay installed - reassigning
+            // a condition doesn't replace an earlier one, so drop the first
+            // guard before installing another for the same condition.
+            helper_functions.push(quote! {
+                #[cfg(test)]
+                #[allow(dead_code)]
+                pub fn #set_mock_when_helper<M, F>(matcher: M, mock: F) -> ::fnmock::MockGuard
+                where
+                    #matcher_where,
+                    #helper_where,
+                {
+                    let arc_matcher: ::std::sync::Arc<#matcher_fn_type> = ::std::sync::Arc::new(matcher);
+                    let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                        ::std::sync::Arc::new(::std::sync::Mutex::new(mock));
+                    ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                        #mock_key, arc_matcher, arc_mock, false,
+                    )
+                }
+            });
+
+            // Helpers for the common "always return this value"/"always error" cases
+            let set_mock_returning_helper =
+                syn::Ident::new(&format!("set_mock_{}_returning", fn_name), fn_name.span());
+            helper_functions.push(quote! {
+                #[cfg(test)]
+                #[allow(dead_code)]
+                pub fn #set_mock_returning_helper(value: #mock_return_type) -> ::fnmock::MockGuard
+                where
+                    #mock_return_type: Clone + Send + Sync + 'static,
+                {
+                    let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                        ::std::sync::Arc::new(::std::sync::Mutex::new(move |#(#ignored_params),*| value.clone()));
+                    let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                        ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                    ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                        #mock_key,
+                        arc_matcher,
+                        arc_mock,
+                        true,
+                    )
+                }
+            });
+
+            if let Some((ok_ty, err_ty)) = &result_types {
+                let set_mock_ok_helper =
+                    syn::Ident::new(&format!("set_mock_{}_ok", fn_name), fn_name.span());
+                let set_mock_err_helper =
+                    syn::Ident::new(&format!("set_mock_{}_err", fn_name), fn_name.span());
+                helper_functions.push(quote! {
+                    #[cfg(test)]
+                    #[allow(dead_code)]
+                    pub fn #set_mock_ok_helper(value: #ok_ty) -> ::fnmock::MockGuard
+                    where
+                        #ok_ty: Clone + Send + Sync + 'static,
+                        #err_ty: Send + Sync + 'static,
+                    {
+                        let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                            ::std::sync::Arc::new(::std::sync::Mutex::new(move |#(#ignored_params),*| Ok(value.clone())));
+                        let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                            ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                        ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                            #mock_key,
+                            arc_matcher,
+                            arc_mock,
+                            true,
+                        )
+                    }
+
+                    #[cfg(test)]
+                    #[allow(dead_code)]
+                    pub fn #set_mock_err_helper(value: #err_ty) -> ::fnmock::MockGuard
+                    where
+                        #ok_ty: Send + Sync + 'static,
+                        #err_ty: Clone + Send + Sync + 'static,
+                    {
+                        let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                            ::std::sync::Arc::new(::std::sync::Mutex::new(move |#(#ignored_params),*| Err(value.clone())));
+                        let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                            ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                        ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                            #mock_key,
+                            arc_matcher,
+                            arc_mock,
+                            true,
+                        )
+                    }
+                });
+            }
+
+            // `FnMut` mocks (and the `_seq` helper built on top of them) serialize
+            // concurrent calls through a `Mutex`, since an `FnMut` can't be shared
+            // across threads like `Fn` can. They're installed as a catch-all
+            // `MockList` entry the same way the helpers above are, rather than
+            // through a separate registry, so a `_mut`/`_seq` mock and a
+            // `_when`/plain mock for the same function can't silently
+            // clobber each other.
+            let set_mock_mut_helper =
+                syn::Ident::new(&format!("set_mock_{}_mut", fn_name), fn_name.span());
+            let set_mock_seq_helper =
+                syn::Ident::new(&format!("set_mock_{}_seq", fn_name), fn_name.span());
+            helper_functions.push(quote! {
+                /// Like the `Fn`-based helper, but the mock may mutate its
+                /// own state across calls. Concurrent calls are serialized
+                /// through a `Mutex`.
+                #[cfg(test)]
+                #[allow(dead_code)]
+                pub fn #set_mock_mut_helper<F>(mock: F) -> ::fnmock::MockGuard
+                where
+                    #mut_helper_where,
+                {
+                    let arc_mock: ::std::sync::Arc<#mock_fn_type> =
+                        ::std::sync::Arc::new(::std::sync::Mutex::new(mock));
+                    let arc_matcher: ::std::sync::Arc<#matcher_fn_type> =
+                        ::std::sync::Arc::new(|#(#ignored_matcher_params),*| true);
+                    ::fnmock::MockRegistry::set_mock_when::<#matcher_fn_type, #mock_fn_type>(
+                        #mock_key,
+                        arc_matcher,
+                        arc_mock,
+                        true,
+                    )
+                }
+
+                /// Yields each value in `values` once, in order, then panics.
+                /// Calls are serialized through a `Mutex`.
+                #[cfg(test)]
+                #[allow(dead_code)]
+                pub fn #set_mock_seq_helper(values: ::std::vec::Vec<#mock_return_type>) -> ::fnmock::MockGuard
+                where
+                    #mock_return_type: Send + 'static,
+                {
+                    let mut values = values.into_iter();
+                    Self::#set_mock_mut_helper(move |#(#ignored_params),*| {
+                        values
+                            .next()
+                            .expect(concat!("mock `", #fn_name_str, "` sequence exhausted"))
+                    })
                 }
             });
         } else {
@@ -335,14 +1042,28 @@ fn generate_mockable_impl(input_impl: ItemImpl) -> TokenStream {
 
     let expanded = quote! {
         #[cfg(not(test))]
-        impl #impl_generics #self_ty #ty_generics #where_clause {
+        impl #impl_generics #trait_for #self_ty #ty_generics #where_clause {
             #(#non_test_items)*
         }
 
         #[cfg(test)]
-        impl #impl_generics #self_ty #ty_generics #where_clause {
+        impl #impl_generics #trait_for #self_ty #ty_generics #where_clause {
             #(#test_items)*
+        }
+
+        // The renamed originals can't live alongside the wrapper inside a
+        // trait impl block - a trait impl may only contain members of the
+        // trait itself - so, like the `set_mock_*` helpers below, they
+        // always get their own inherent impl block.
+        #[cfg(test)]
+        impl #impl_generics #self_ty #ty_generics #where_clause {
+            #(#original_items)*
+        }
 
+        // `set_mock_*` helpers can't be free associated fns on a trait impl,
+        // so they always get their own inherent impl block.
+        #[cfg(test)]
+        impl #impl_generics #self_ty #ty_generics #where_clause {
             #(#helper_functions)*
         }
     };
@@ -1,37 +1,186 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, LazyLock, Mutex};
 
 // Re-export the macro from fnmock-macro
 pub use fnmock_macro::mockable;
 
-pub struct MockWrapper<F: ?Sized>(pub Arc<F>);
+/// One conditional mock installed via `set_mock_when`-style helpers: it
+/// applies to a call only when `matcher` returns true for that call's
+/// arguments.
+///
+/// `Matcher` is a `dyn Fn(...) -> bool` trait object matching the mocked
+/// function's own parameter list by reference (e.g. `dyn Fn(&str) -> bool`),
+/// while `F` is the `Mutex`-wrapped `dyn FnMut` the mock itself is stored
+/// as, taking the owned-converted parameters. Unlike a literal
+/// tuple of argument references, such a trait object is `'static`
+/// regardless of the borrow lifetimes at any particular call site - Rust
+/// elides each `&_` parameter to a higher-ranked `for<'r> Fn(&'r _) -> bool`,
+/// which is exactly what lets it live behind `Box<dyn Any + Send>` and be
+/// downcast back out of it.
+struct MockEntry<Matcher: ?Sized, F: ?Sized> {
+    id: u64,
+    matcher: Arc<Matcher>,
+    mock: Arc<F>,
+    count: Arc<AtomicUsize>,
+    // Set for the always-true matcher installed by `set_mock`/`_returning`/
+    // `_ok`/`_err`/`_mut`-style helpers, as opposed to a real `_when`
+    // condition. Lets `set_mock_when` tell the two apart so that installing
+    // a new catch-all replaces an older one instead of merely shadowing it.
+    catch_all: bool,
+}
+
+/// The ordered list of conditional mocks installed for a single function
+/// key. Entries are tried in install order; the first whose matcher matches
+/// wins.
+pub struct MockList<Matcher: ?Sized, F: ?Sized> {
+    entries: Mutex<Vec<MockEntry<Matcher, F>>>,
+}
+
+impl<Matcher: ?Sized, F: ?Sized> MockList<Matcher, F> {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+}
 
-// Global mock storage
+// Monotonically increasing ids distinguishing entries within a `MockList`, so
+// a `MockGuard` can remove exactly the entry it installed and leave sibling
+// entries (earlier or later `_when` registrations) intact.
+static NEXT_MOCK_ID: AtomicU64 = AtomicU64::new(0);
+
+// Global mock storage, consulted as a fallback when a thread-local mock
+// (see `LOCAL_MOCKS`) hasn't been installed for the calling thread. Mocks
+// land here only via `MockGuard::shared`.
 static MOCKS: LazyLock<Mutex<HashMap<String, Box<dyn std::any::Any + Send>>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+thread_local! {
+    // Per-thread mock storage. Mocks are installed here by default so that
+    // parallel tests don't observe each other's mocks for the same function.
+    static LOCAL_MOCKS: RefCell<HashMap<String, Box<dyn std::any::Any + Send>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// How a `MockGuard` removes its own entry from its `MockList` on drop. Every
+/// mock - plain, `_when`, `_mut`/`_seq` alike - is one `MockEntry` in the
+/// `MockList` for its key, so removal is always "drop this one entry",
+/// never "drop the whole key".
+struct Removal {
+    // Tries removing from both the thread-local and shared registries;
+    // harmless if the entry isn't in one of them, so callers don't need to
+    // track which scope it currently lives in.
+    remove: Box<dyn FnMut() + Send>,
+    // Consumed by `MockGuard::shared` to move the entry from the
+    // thread-local list into the shared one.
+    promote: Option<Box<dyn FnOnce() + Send>>,
+}
+
+/// An expectation on how many times a mock should be called, checked when
+/// its `MockGuard` is dropped.
+enum Expectation {
+    Exact(usize),
+    AtLeast(usize),
+}
+
 /// Guard that removes a mock when dropped.
 /// This ensures mocks are automatically cleaned up at the end of a test.
+///
+/// Optionally carries a call-count expectation set via [`MockGuard::times`],
+/// [`MockGuard::times_at_least`] or [`MockGuard::never`]; if the observed
+/// call count doesn't satisfy it, drop panics.
 #[must_use = "MockGuard must be held for the duration of the mock"]
 pub struct MockGuard {
     name: String,
+    count: Arc<AtomicUsize>,
+    expectation: Option<Expectation>,
+    removal: Removal,
 }
 
 impl MockGuard {
-    fn new(name: String) -> Self {
-        Self { name }
+    fn new_matched(
+        name: String,
+        count: Arc<AtomicUsize>,
+        remove: Box<dyn FnMut() + Send>,
+        promote: Box<dyn FnOnce() + Send>,
+    ) -> Self {
+        Self {
+            name,
+            count,
+            expectation: None,
+            removal: Removal {
+                remove,
+                promote: Some(promote),
+            },
+        }
     }
 
     /// Name/key of this mock
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// Promote this mock into the global registry, so that threads spawned
+    /// by the code under test (which don't share the installing thread's
+    /// thread-local mocks) can see it too. The mock is consulted as a
+    /// fallback whenever a thread has no matching thread-local mock of its
+    /// own.
+    pub fn shared(mut self) -> Self {
+        if let Some(promote) = self.removal.promote.take() {
+            promote();
+        }
+        self
+    }
+
+    /// Number of times the mock has been called so far.
+    pub fn call_count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Expect the mock to be called exactly `n` times.
+    pub fn times(mut self, n: usize) -> Self {
+        self.expectation = Some(Expectation::Exact(n));
+        self
+    }
+
+    /// Expect the mock to be called at least `n` times.
+    pub fn times_at_least(mut self, n: usize) -> Self {
+        self.expectation = Some(Expectation::AtLeast(n));
+        self
+    }
+
+    /// Expect the mock to never be called.
+    pub fn never(self) -> Self {
+        self.times(0)
+    }
 }
 
 impl Drop for MockGuard {
     fn drop(&mut self) {
-        let mut mocks = MOCKS.lock().unwrap();
-        mocks.remove(&self.name);
+        (self.removal.remove)();
+
+        if std::thread::panicking() {
+            return;
+        }
+
+        let count = self.call_count();
+        match &self.expectation {
+            Some(Expectation::Exact(n)) if count != *n => {
+                panic!(
+                    "mock `{}` expected {} call(s), but was called {} time(s)",
+                    self.name, n, count
+                );
+            }
+            Some(Expectation::AtLeast(n)) if count < *n => {
+                panic!(
+                    "mock `{}` expected at least {} call(s), but was called {} time(s)",
+                    self.name, n, count
+                );
+            }
+            _ => {}
+        }
     }
 }
 
@@ -39,28 +188,158 @@ impl Drop for MockGuard {
 pub struct MockRegistry;
 
 impl MockRegistry {
-    /// Set a mock that's already wrapped in Arc (used by macro-generated helper)
-    /// Returns a guard that removes the mock when dropped
-    pub fn set_mock<F: ?Sized>(name: &str, mock: Arc<F>) -> MockGuard
+    /// Install a conditional mock: `matcher` is consulted on every call and
+    /// decides whether this entry applies. `matcher` and `mock` are already
+    /// wrapped in `Arc` by the macro-generated helper: `matcher` as a `dyn
+    /// Fn` trait object shaped like the mocked function's own parameter list
+    /// and returning `bool`, `mock` as a `Mutex`-wrapped `dyn FnMut` over the
+    /// owned-converted parameters returning the (owned-converted) return
+    /// type - trait objects like these stay `'static` no matter what borrows
+    /// the call site passes through them.
+    ///
+    /// Entries registered for the same key are tried in install order, and
+    /// the first whose matcher returns true wins; `set_mock`-style helpers
+    /// are implemented on top of this with an always-true matcher, so a
+    /// catch-all mock installed after more specific `_when` mocks acts as
+    /// their fallback.
+    ///
+    /// `catch_all` marks `matcher` as one of those always-true matchers
+    /// rather than a real `_when` condition. Installing a new catch-all
+    /// entry first removes any older catch-all entry for this key, so
+    /// re-calling a plain/`_returning`/`_ok`/`_err`/`_mut` helper while the
+    /// previous guard is still alive replaces its mock, the same
+    /// "last write wins" behavior those helpers had before they were backed
+    /// by this shared registry. `_when` entries are never evicted this way -
+    /// they only stop applying when their own guard drops.
+    ///
+    /// Installed in the calling thread's thread-local registry by default;
+    /// call `.shared()` on the returned guard to make it visible to every
+    /// thread.
+    pub fn set_mock_when<Matcher: ?Sized, F: ?Sized>(
+        name: &str,
+        matcher: Arc<Matcher>,
+        mock: Arc<F>,
+        catch_all: bool,
+    ) -> MockGuard
     where
+        Matcher: 'static + Send + Sync,
         F: 'static + Send + Sync,
     {
-        let mut mocks = MOCKS.lock().unwrap();
-        let wrapped = MockWrapper(mock);
-        mocks.insert(name.to_string(), Box::new(wrapped));
-        MockGuard::new(name.to_string())
+        let id = NEXT_MOCK_ID.fetch_add(1, Ordering::SeqCst);
+        let count = Arc::new(AtomicUsize::new(0));
+        let entry = MockEntry {
+            id,
+            matcher,
+            mock,
+            count: Arc::clone(&count),
+            catch_all,
+        };
+
+        LOCAL_MOCKS.with(|mocks| {
+            let mut mocks = mocks.borrow_mut();
+            let boxed = mocks
+                .entry(name.to_string())
+                .or_insert_with(|| Box::new(MockList::<Matcher, F>::new()));
+            if let Some(list) = boxed.downcast_mut::<MockList<Matcher, F>>() {
+                let mut entries = list.entries.lock().unwrap();
+                if catch_all {
+                    entries.retain(|e| !e.catch_all);
+                }
+                entries.push(entry);
+            }
+        });
+
+        let name_owned = name.to_string();
+        let remove_name = name_owned.clone();
+        let remove = move || {
+            LOCAL_MOCKS.with(|mocks| {
+                if let Some(boxed) = mocks.borrow().get(&remove_name) {
+                    if let Some(list) = boxed.downcast_ref::<MockList<Matcher, F>>() {
+                        list.entries.lock().unwrap().retain(|e| e.id != id);
+                    }
+                }
+            });
+            let mocks = MOCKS.lock().unwrap();
+            if let Some(boxed) = mocks.get(&remove_name) {
+                if let Some(list) = boxed.downcast_ref::<MockList<Matcher, F>>() {
+                    list.entries.lock().unwrap().retain(|e| e.id != id);
+                }
+            }
+        };
+        let promote = move || {
+            let entry = LOCAL_MOCKS.with(|mocks| {
+                let mocks = mocks.borrow();
+                mocks.get(&name_owned).and_then(|boxed| {
+                    boxed
+                        .downcast_ref::<MockList<Matcher, F>>()
+                        .and_then(|list| {
+                            let mut entries = list.entries.lock().unwrap();
+                            let pos = entries.iter().position(|e| e.id == id)?;
+                            Some(entries.remove(pos))
+                        })
+                })
+            });
+            let Some(entry) = entry else { return };
+            let mut mocks = MOCKS.lock().unwrap();
+            let boxed = mocks
+                .entry(name_owned.clone())
+                .or_insert_with(|| Box::new(MockList::<Matcher, F>::new()));
+            if let Some(list) = boxed.downcast_mut::<MockList<Matcher, F>>() {
+                let mut entries = list.entries.lock().unwrap();
+                if entry.catch_all {
+                    entries.retain(|e| !e.catch_all);
+                }
+                entries.push(entry);
+            }
+        };
+
+        MockGuard::new_matched(name.to_string(), count, Box::new(remove), Box::new(promote))
     }
 
-    /// Get a mock for a specific function
-    /// F should be the trait object type (dyn Fn(...) -> Ret)
-    pub fn get_mock<F>(name: &str) -> Option<Arc<F>>
+    /// Find the first installed entry for `name` whose matcher matches the
+    /// current call, along with its call-count counter. `is_match` is given
+    /// each entry's matcher in turn (e.g. `|m| m(&url)`) and decides whether
+    /// it applies; the macro-generated wrapper builds this closure so it
+    /// alone needs to know the mocked function's concrete parameter list.
+    /// Checks the calling thread's thread-local entries first, falling back
+    /// to the shared/global ones (populated via `MockGuard::shared`).
+    pub fn get_mock_when<Matcher: ?Sized, F: ?Sized>(
+        name: &str,
+        is_match: impl Fn(&Matcher) -> bool,
+    ) -> Option<(Arc<F>, Arc<AtomicUsize>)>
     where
-        F: ?Sized + Send + Sync + 'static,
+        Matcher: 'static + Send + Sync,
+        F: 'static + Send + Sync,
     {
+        fn find<Matcher: ?Sized, F: ?Sized>(
+            boxed: &Box<dyn std::any::Any + Send>,
+            is_match: &impl Fn(&Matcher) -> bool,
+        ) -> Option<(Arc<F>, Arc<AtomicUsize>)>
+        where
+            Matcher: 'static + Send + Sync,
+            F: 'static + Send + Sync,
+        {
+            let list = boxed.downcast_ref::<MockList<Matcher, F>>()?;
+            let entries = list.entries.lock().unwrap();
+            entries
+                .iter()
+                .find(|entry| is_match(&*entry.matcher))
+                .map(|entry| (Arc::clone(&entry.mock), Arc::clone(&entry.count)))
+        }
+
+        let local = LOCAL_MOCKS.with(|mocks| {
+            mocks
+                .borrow()
+                .get(name)
+                .and_then(|boxed| find::<Matcher, F>(boxed, &is_match))
+        });
+        if local.is_some() {
+            return local;
+        }
+
         let mocks = MOCKS.lock().unwrap();
         mocks
             .get(name)
-            .and_then(|boxed| boxed.downcast_ref::<MockWrapper<F>>())
-            .map(|wrapper| Arc::clone(&wrapper.0))
+            .and_then(|boxed| find::<Matcher, F>(boxed, &is_match))
     }
 }
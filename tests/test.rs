@@ -5,6 +5,12 @@ fn fetch_data(url: &str) -> String {
     format!("Real data from {}", url)
 }
 
+#[mockable]
+fn fetch_count(url: &str) -> Result<u32, String> {
+    let _ = url;
+    Ok(42)
+}
+
 pub struct A {
     x: i32,
 }
@@ -18,6 +24,14 @@ impl A {
     pub async fn async_test(&self) -> i32 {
         self.x
     }
+
+    // A `&str` return with no input reference to elide its lifetime from
+    // (its lifetime is implicitly tied to `&self`) - a mock closure can't
+    // conjure such a borrow on its own, so this only became mockable once
+    // the return type is normalized to an owned `String` behind the scenes.
+    pub fn label(&self) -> &str {
+        "real label"
+    }
 }
 
 #[test]
@@ -51,3 +65,172 @@ async fn test_struct_mocked() {
     assert_eq!(a.test(), 20);
     assert_eq!(a.async_test().await, 20);
 }
+
+#[test]
+fn test_call_count_tracking() {
+    let g = set_mock_fetch_data(|url| format!("Mocked data from {}", url));
+
+    assert_eq!(g.call_count(), 0);
+    fetch_data("a.com");
+    fetch_data("b.com");
+    assert_eq!(g.call_count(), 2);
+
+    let _g = g.times(2);
+}
+
+#[test]
+#[should_panic(expected = "expected 3 call(s), but was called 1 time(s)")]
+fn test_call_count_expectation_panics() {
+    let g = set_mock_fetch_data(|url| format!("Mocked data from {}", url)).times(3);
+    fetch_data("a.com");
+    drop(g);
+}
+
+#[test]
+fn test_mock_is_thread_local_by_default() {
+    let _g = set_mock_fetch_data(|url| format!("Mocked data from {}", url));
+    assert_eq!(fetch_data("test.com"), "Mocked data from test.com");
+
+    // A different thread has no thread-local mock installed, so it sees the
+    // real implementation even while the mock above is still alive.
+    let handle = std::thread::spawn(|| fetch_data("other.com"));
+    assert_eq!(handle.join().unwrap(), "Real data from other.com");
+}
+
+#[test]
+fn test_shared_mock_is_visible_to_worker_threads() {
+    let _g = set_mock_fetch_data(|url| format!("Mocked data from {}", url)).shared();
+
+    let handle = std::thread::spawn(|| fetch_data("other.com"));
+    assert_eq!(handle.join().unwrap(), "Mocked data from other.com");
+}
+
+trait Greeter {
+    fn greet(&self) -> String;
+}
+
+#[mockable]
+impl Greeter for A {
+    fn greet(&self) -> String {
+        format!("Hello, {}", self.x)
+    }
+}
+
+#[test]
+fn test_trait_impl_mocked() {
+    let a = A { x: 10 };
+
+    // Original behavior
+    assert_eq!(a.greet(), "Hello, 10");
+    assert_eq!(a.test(), 10);
+
+    // Mocking `Greeter::greet` doesn't collide with `A::test`'s own "test" key
+    let _g = A::set_mock_greet(|_self| "mocked greeting".to_string());
+
+    assert_eq!(a.greet(), "mocked greeting");
+    assert_eq!(a.test(), 10);
+}
+
+#[test]
+fn test_returning_helper() {
+    let _g = set_mock_fetch_data_returning("always this".to_string());
+    assert_eq!(fetch_data("a.com"), "always this");
+    assert_eq!(fetch_data("b.com"), "always this");
+}
+
+#[test]
+fn test_seq_helper_yields_values_in_order_then_panics() {
+    let g = set_mock_fetch_data_seq(vec!["first".to_string(), "second".to_string()]);
+
+    assert_eq!(fetch_data("a.com"), "first");
+    assert_eq!(fetch_data("b.com"), "second");
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| fetch_data("c.com")));
+    assert!(result.is_err());
+
+    drop(g);
+}
+
+#[test]
+fn test_when_helper_dispatches_by_argument() {
+    let _specific =
+        set_mock_fetch_data_when(|url: &str| url == "special.com", |_url| "special response".to_string());
+    let _catch_all = set_mock_fetch_data_returning("fallback response".to_string());
+
+    assert_eq!(fetch_data("special.com"), "special response");
+    assert_eq!(fetch_data("other.com"), "fallback response");
+}
+
+#[test]
+fn test_when_helper_on_method() {
+    let a = A { x: 10 };
+
+    let _g = A::set_mock_test_when(|a: &A| a.x == 10, |_self| 99);
+
+    assert_eq!(a.test(), 99);
+}
+
+#[test]
+fn test_owned_conversion_enables_mocking_borrowed_return() {
+    let a = A { x: 10 };
+
+    assert_eq!(a.label(), "real label");
+
+    // The helper's closure returns an owned `String` - the wrapper leaks it
+    // to satisfy the `&str` the real signature promises.
+    let _g = A::set_mock_label(|_self| "mocked label".to_string());
+    assert_eq!(a.label(), "mocked label");
+}
+
+#[test]
+fn test_owned_conversion_adapts_borrowed_params() {
+    // `url: &str` is normalized to `String` in the mock closure's signature,
+    // so the closure can own and inspect it without juggling lifetimes.
+    let _g = set_mock_fetch_data(|url: String| format!("len={}", url.len()));
+    assert_eq!(fetch_data("abcde"), "len=5");
+}
+
+#[test]
+fn test_mut_and_when_mocks_share_one_registry() {
+    // A `_when` mock installed first still fires once a `_mut` catch-all is
+    // layered on top of it, and the `_mut` mock still backs up non-matching
+    // calls - both kinds are entries in the same `MockList` for this key.
+    let _specific =
+        set_mock_fetch_data_when(|url: &str| url == "special.com", |_url| "special response".to_string());
+    let mut calls = 0;
+    let _catch_all = set_mock_fetch_data_mut(move |url: String| {
+        calls += 1;
+        format!("mut response #{} for {}", calls, url)
+    });
+
+    assert_eq!(fetch_data("special.com"), "special response");
+    assert_eq!(fetch_data("other.com"), "mut response #1 for other.com");
+    assert_eq!(fetch_data("other.com"), "mut response #2 for other.com");
+}
+
+#[test]
+fn test_reinstalling_catch_all_mock_replaces_the_previous_one() {
+    // A second plain/`_returning`/`_ok`/`_err`/`_mut` guard for the same
+    // function takes over immediately, even while the first guard is still
+    // alive - catch-all entries don't stack like `_when` entries do.
+    let _first = set_mock_fetch_data(|url| format!("first: {}", url));
+    assert_eq!(fetch_data("a.com"), "first: a.com");
+
+    let _second = set_mock_fetch_data_returning("second".to_string());
+    assert_eq!(fetch_data("a.com"), "second");
+}
+
+#[test]
+fn test_ok_err_helpers() {
+    assert_eq!(fetch_count("a.com"), Ok(42));
+
+    {
+        let _g = set_mock_fetch_count_ok(7);
+        assert_eq!(fetch_count("a.com"), Ok(7));
+    }
+
+    {
+        let _g = set_mock_fetch_count_err("boom".to_string());
+        assert_eq!(fetch_count("a.com"), Err("boom".to_string()));
+    }
+}